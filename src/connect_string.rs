@@ -0,0 +1,108 @@
+//! Parsing of ZooKeeper "connect strings": a comma-separated list of
+//! `host:port` pairs identifying the servers in an ensemble, with an
+//! optional `/chroot` suffix that is transparently prepended to every path
+//! used over the resulting connection.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// A parsed connect string, as accepted by
+/// [`ZooKeeper::connect`](../struct.ZooKeeper.html#method.connect).
+#[derive(Debug, Clone)]
+pub(crate) struct ConnectString {
+    /// The resolved addresses of every server in the ensemble, in the order
+    /// they were given.
+    pub(crate) addrs: Vec<SocketAddr>,
+    /// The root under which every path on this connection is rooted, if
+    /// one was given.
+    pub(crate) chroot: Option<String>,
+}
+
+impl ConnectString {
+    pub(crate) fn parse(s: &str) -> Result<Self, failure::Error> {
+        let (hosts, chroot) = match s.find('/') {
+            Some(i) => (&s[..i], Some(&s[i..])),
+            None => (s, None),
+        };
+
+        let chroot = match chroot {
+            None | Some("/") => None,
+            Some(chroot) => {
+                if chroot.len() > 1 && chroot.ends_with('/') {
+                    bail!("chroot {:?} must not end with a trailing slash", chroot);
+                }
+                Some(chroot.to_string())
+            }
+        };
+
+        let mut addrs = Vec::new();
+        for host in hosts.split(',') {
+            let host = host.trim();
+            if host.is_empty() {
+                continue;
+            }
+            let resolved = host
+                .to_socket_addrs()
+                .map_err(|e| format_err!("could not resolve zookeeper host {:?}: {}", host, e))?;
+            addrs.extend(resolved);
+        }
+
+        if addrs.is_empty() {
+            bail!("connect string {:?} did not name any servers", s);
+        }
+
+        Ok(ConnectString { addrs, chroot })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_single_host_with_no_chroot() {
+        let cs = ConnectString::parse("127.0.0.1:2181").unwrap();
+        assert_eq!(cs.addrs, vec!["127.0.0.1:2181".parse().unwrap()]);
+        assert_eq!(cs.chroot, None);
+    }
+
+    #[test]
+    fn parses_multiple_hosts_with_a_chroot() {
+        let cs = ConnectString::parse("127.0.0.1:2181,127.0.0.2:2182/a/b").unwrap();
+        assert_eq!(
+            cs.addrs,
+            vec![
+                "127.0.0.1:2181".parse().unwrap(),
+                "127.0.0.2:2182".parse().unwrap(),
+            ]
+        );
+        assert_eq!(cs.chroot, Some("/a/b".to_string()));
+    }
+
+    #[test]
+    fn trims_whitespace_around_hosts() {
+        let cs = ConnectString::parse(" 127.0.0.1:2181 , 127.0.0.2:2182 ").unwrap();
+        assert_eq!(
+            cs.addrs,
+            vec![
+                "127.0.0.1:2181".parse().unwrap(),
+                "127.0.0.2:2182".parse().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_bare_slash_is_not_a_chroot() {
+        let cs = ConnectString::parse("127.0.0.1:2181/").unwrap();
+        assert_eq!(cs.chroot, None);
+    }
+
+    #[test]
+    fn rejects_a_chroot_with_a_trailing_slash() {
+        assert!(ConnectString::parse("127.0.0.1:2181/a/").is_err());
+    }
+
+    #[test]
+    fn rejects_a_connect_string_with_no_servers() {
+        assert!(ConnectString::parse("").is_err());
+    }
+}
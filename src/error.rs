@@ -0,0 +1,99 @@
+//! Errors returned by the various operations exposed by this crate.
+//!
+//! Any error not captured by one of these fine-grained enums (for example, an
+//! I/O error, or a response that does not match the shape of its request) is
+//! surfaced as a `failure::Error` instead -- those are considered bugs in
+//! this crate or unrecoverable connection failures, not something a caller
+//! should be expected to match on.
+
+/// Errors from a [`ZooKeeper::create`](../struct.ZooKeeper.html#method.create)
+/// call.
+#[derive(Debug, Clone, Eq, PartialEq, Fail)]
+pub enum Create {
+    /// A node with the given path already exists.
+    #[fail(display = "a node already exists at the given path")]
+    NodeExists,
+    /// The parent node does not exist.
+    #[fail(display = "the parent node does not exist")]
+    NoNode,
+    /// The given ACL is invalid.
+    #[fail(display = "the given acl is invalid")]
+    InvalidAcl,
+    /// The parent node is ephemeral, and so cannot have children.
+    #[fail(display = "the parent node is ephemeral")]
+    NoChildrenForEphemerals,
+}
+
+/// Errors from a [`ZooKeeper::delete`](../struct.ZooKeeper.html#method.delete)
+/// call.
+#[derive(Debug, Clone, Eq, PartialEq, Fail)]
+pub enum Delete {
+    /// No node exists at the given path.
+    #[fail(display = "no node exists at the given path")]
+    NoNode,
+    /// The given version did not match the node's actual version.
+    #[fail(display = "the given version ({}) did not match", expected)]
+    BadVersion {
+        /// The version that was passed in to the `delete` call.
+        expected: i32,
+    },
+    /// The given node has children, and so cannot be deleted.
+    #[fail(display = "the node has children")]
+    NotEmpty,
+}
+
+/// Errors from a [`ZooKeeper::set_data`](../struct.ZooKeeper.html#method.set_data)
+/// call.
+#[derive(Debug, Clone, Eq, PartialEq, Fail)]
+pub enum SetData {
+    /// No node exists at the given path.
+    #[fail(display = "no node exists at the given path")]
+    NoNode,
+    /// The given version did not match the node's actual version.
+    #[fail(display = "the given version ({}) did not match", expected)]
+    BadVersion {
+        /// The version that was passed in to the `set_data` call.
+        expected: i32,
+    },
+}
+
+/// Errors from a [`ZooKeeper::set_acl`](../struct.ZooKeeper.html#method.set_acl)
+/// call.
+#[derive(Debug, Clone, Eq, PartialEq, Fail)]
+pub enum SetAcl {
+    /// No node exists at the given path.
+    #[fail(display = "no node exists at the given path")]
+    NoNode,
+    /// The given version did not match the node's actual ACL version.
+    #[fail(display = "the given version ({}) did not match", expected)]
+    BadVersion {
+        /// The version that was passed in to the `set_acl` call.
+        expected: i32,
+    },
+    /// The given ACL is invalid.
+    #[fail(display = "the given acl is invalid")]
+    InvalidAcl,
+}
+
+/// Errors from a [`ZooKeeper::add_auth`](../struct.ZooKeeper.html#method.add_auth)
+/// call.
+#[derive(Debug, Clone, Eq, PartialEq, Fail)]
+pub enum Auth {
+    /// The server rejected the supplied credentials.
+    #[fail(display = "the server rejected the supplied credentials")]
+    Failed,
+}
+
+/// Errors from a [`Multi::run`](../struct.Multi.html#method.run) call.
+///
+/// A transaction either succeeds as a whole, or fails as a whole; if any
+/// operation fails, every operation in the transaction (including those that
+/// would otherwise have succeeded) is rolled back.
+#[derive(Debug, Clone, Eq, PartialEq, Fail)]
+#[fail(display = "operation {} in the transaction failed: {}", index, source)]
+pub struct Multi {
+    /// The index of the operation that caused the transaction to fail.
+    pub index: usize,
+    /// A description of the error that operation produced.
+    pub source: String,
+}
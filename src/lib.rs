@@ -1,22 +1,29 @@
 extern crate byteorder;
+extern crate bytes;
 #[macro_use]
 extern crate failure;
 #[macro_use]
 extern crate futures;
+extern crate rustls;
 extern crate tokio;
+extern crate tokio_codec;
+extern crate tokio_rustls;
+extern crate webpki;
 #[macro_use]
 extern crate lazy_static;
 
-use futures::sync::oneshot;
+use futures::sync::{mpsc, oneshot};
 use std::borrow::Cow;
-use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::prelude::*;
 
+mod connect_string;
 pub mod error;
 mod proto;
 mod types;
 
-use proto::{WatchType, ZkError};
+use connect_string::ConnectString;
+use proto::{Watch, WatchType, ZkError};
 pub use types::{Acl, CreateMode, KeeperState, Stat, WatchedEvent, WatchedEventType};
 
 #[derive(Clone)]
@@ -26,23 +33,70 @@ pub struct ZooKeeper {
 }
 
 impl ZooKeeper {
+    /// Connect to a ZooKeeper ensemble.
+    ///
+    /// `connect_string` is a comma-separated list of `host:port` pairs
+    /// naming the servers in the ensemble (e.g.
+    /// `"zk1:2181,zk2:2181,zk3:2181"`), optionally followed by a
+    /// `/chroot_path`. When a chroot path is given, it is transparently
+    /// prepended to every path used over the resulting connection, so that
+    /// the client only ever sees and manipulates paths relative to it.
     pub fn connect(
-        addr: &SocketAddr,
+        connect_string: &str,
     ) -> impl Future<Item = (Self, impl Stream<Item = WatchedEvent, Error = ()>), Error = failure::Error>
     {
         let (tx, rx) = futures::sync::mpsc::unbounded();
-        let addr = addr.clone();
-        tokio::net::TcpStream::connect(&addr)
-            .map_err(failure::Error::from)
-            .and_then(move |stream| Self::handshake(addr, stream, tx))
+        future::result(ConnectString::parse(connect_string)).and_then(move |cs| {
+            let addr = cs.addrs[0];
+            let connector: Box<proto::Connector<tokio::net::TcpStream>> =
+                Box::new(proto::PlainConnector);
+            connector
+                .connect(addr)
+                .map_err(failure::Error::from)
+                .and_then(move |stream| Self::handshake(cs, stream, connector, tx))
+        }).map(move |zk| (zk, rx))
+    }
+
+    /// Connect to a ZooKeeper ensemble over TLS, as `connect` does over
+    /// plain TCP.
+    ///
+    /// `tls_config` is handed to `tokio-rustls`, and `server_name` is the
+    /// name the server's certificate is checked against.
+    pub fn connect_tls(
+        connect_string: &str,
+        tls_config: Arc<rustls::ClientConfig>,
+        server_name: &str,
+    ) -> impl Future<Item = (Self, impl Stream<Item = WatchedEvent, Error = ()>), Error = failure::Error>
+    {
+        let (tx, rx) = futures::sync::mpsc::unbounded();
+        let server_name = server_name.to_string();
+        future::result(ConnectString::parse(connect_string))
+            .join(future::result(
+                webpki::DNSNameRef::try_from_ascii_str(&server_name)
+                    .map(|n| n.to_owned())
+                    .map_err(|_| format_err!("{:?} is not a valid DNS name", server_name)),
+            ))
+            .and_then(move |(cs, domain)| {
+                let addr = cs.addrs[0];
+                let connector: Box<proto::Connector<_>> =
+                    Box::new(proto::TlsStreamConnector::new(tls_config, domain));
+                connector
+                    .connect(addr)
+                    .map_err(failure::Error::from)
+                    .and_then(move |stream| Self::handshake(cs, stream, connector, tx))
+            })
             .map(move |zk| (zk, rx))
     }
 
-    fn handshake(
-        addr: SocketAddr,
-        stream: tokio::net::TcpStream,
+    fn handshake<S>(
+        cs: ConnectString,
+        stream: S,
+        connector: Box<proto::Connector<S>>,
         default_watcher: futures::sync::mpsc::UnboundedSender<WatchedEvent>,
-    ) -> impl Future<Item = Self, Error = failure::Error> {
+    ) -> impl Future<Item = Self, Error = failure::Error>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
         let request = proto::Request::Connect {
             protocol_version: 0,
             last_zxid_seen: 0,
@@ -51,11 +105,8 @@ impl ZooKeeper {
             passwd: vec![],
             read_only: false,
         };
-        eprintln!("about to handshake");
-
-        let enqueuer = proto::Packetizer::new(addr, stream, default_watcher);
-        enqueuer.enqueue(request).map(move |response| {
-            eprintln!("{:?}", response);
+        let enqueuer = proto::Packetizer::new(cs.addrs, cs.chroot, stream, connector, default_watcher);
+        enqueuer.enqueue(request).map(move |_response| {
             ZooKeeper {
                 connection: enqueuer,
             }
@@ -85,7 +136,7 @@ impl ZooKeeper {
                 Ok(r) => bail!("got non-string response to create: {:?}", r),
                 Err(ZkError::NoNode) => Ok(Err(error::Create::NoNode)),
                 Err(ZkError::NodeExists) => Ok(Err(error::Create::NodeExists)),
-                Err(ZkError::InvalidACL) => Ok(Err(error::Create::InvalidAcl)),
+                Err(ZkError::InvalidAcl) => Ok(Err(error::Create::InvalidAcl)),
                 Err(ZkError::NoChildrenForEphemerals) => {
                     Ok(Err(error::Create::NoChildrenForEphemerals))
                 }
@@ -117,6 +168,118 @@ impl ZooKeeper {
             })
             .map(move |r| (self, r))
     }
+
+    pub fn set_data<D>(
+        self,
+        path: &str,
+        version: Option<i32>,
+        data: D,
+    ) -> impl Future<Item = (Self, Result<Stat, error::SetData>), Error = failure::Error>
+    where
+        D: Into<Cow<'static, [u8]>>,
+    {
+        let version = version.unwrap_or(-1);
+        self.connection
+            .enqueue(proto::Request::SetData {
+                path: path.to_string(),
+                data: data.into(),
+                version,
+            })
+            .and_then(move |r| match r {
+                Ok(proto::Response::SetData { stat }) => Ok(Ok(stat)),
+                Ok(r) => bail!("got non-stat response to set-data: {:?}", r),
+                Err(ZkError::NoNode) => Ok(Err(error::SetData::NoNode)),
+                Err(ZkError::BadVersion) => {
+                    Ok(Err(error::SetData::BadVersion { expected: version }))
+                }
+                Err(e) => Err(format_err!("set-data call failed: {:?}", e)),
+            })
+            .map(move |r| (self, r))
+    }
+
+    pub fn get_acl(
+        self,
+        path: &str,
+    ) -> impl Future<Item = (Self, Option<(Vec<Acl>, Stat)>), Error = failure::Error> {
+        self.connection
+            .enqueue(proto::Request::GetAcl {
+                path: path.to_string(),
+            })
+            .and_then(move |r| match r {
+                Ok(proto::Response::GetAcl { acl, stat }) => Ok(Some((acl, stat))),
+                Ok(r) => bail!("got non-acl response to get-acl: {:?}", r),
+                Err(ZkError::NoNode) => Ok(None),
+                Err(e) => Err(format_err!("get-acl call failed: {:?}", e)),
+            })
+            .map(move |r| (self, r))
+    }
+
+    pub fn set_acl<A>(
+        self,
+        path: &str,
+        acl: A,
+        version: Option<i32>,
+    ) -> impl Future<Item = (Self, Result<Stat, error::SetAcl>), Error = failure::Error>
+    where
+        A: Into<Cow<'static, [Acl]>>,
+    {
+        let version = version.unwrap_or(-1);
+        self.connection
+            .enqueue(proto::Request::SetAcl {
+                path: path.to_string(),
+                acl: acl.into(),
+                version,
+            })
+            .and_then(move |r| match r {
+                Ok(proto::Response::SetAcl { stat }) => Ok(Ok(stat)),
+                Ok(r) => bail!("got non-stat response to set-acl: {:?}", r),
+                Err(ZkError::NoNode) => Ok(Err(error::SetAcl::NoNode)),
+                Err(ZkError::BadVersion) => {
+                    Ok(Err(error::SetAcl::BadVersion { expected: version }))
+                }
+                Err(ZkError::InvalidAcl) => Ok(Err(error::SetAcl::InvalidAcl)),
+                Err(e) => Err(format_err!("set-acl call failed: {:?}", e)),
+            })
+            .map(move |r| (self, r))
+    }
+
+    /// Authenticate this session under `scheme` (e.g. `"digest"` or
+    /// `"sasl"`) using `auth`, whose format is scheme-specific.
+    ///
+    /// Multiple credentials may be added to the same session by calling
+    /// this more than once; the ensemble checks requests against every
+    /// credential added so far. Credentials are remembered for the
+    /// lifetime of the connection and are transparently resent if the
+    /// underlying connection is lost and re-established.
+    pub fn add_auth(
+        self,
+        scheme: &str,
+        auth: Vec<u8>,
+    ) -> impl Future<Item = (Self, Result<(), error::Auth>), Error = failure::Error> {
+        self.connection
+            .enqueue(proto::Request::Auth {
+                scheme: scheme.to_string(),
+                auth,
+            })
+            .and_then(move |r| match r {
+                Ok(_) => Ok(Ok(())),
+                Err(ZkError::AuthFailed) => Ok(Err(error::Auth::Failed)),
+                Err(e) => Err(format_err!("add-auth call failed: {:?}", e)),
+            })
+            .map(move |r| (self, r))
+    }
+
+    /// Start building a multi-op transaction.
+    ///
+    /// The operations queued onto the returned [`Multi`] are sent to the
+    /// server together and are applied atomically: either all of them
+    /// succeed, or none of them do.
+    pub fn multi(self) -> Multi {
+        Multi {
+            zk: self,
+            ops: Vec::new(),
+        }
+    }
 }
 
 impl ZooKeeper {
@@ -205,6 +368,30 @@ impl ZooKeeper {
     ) -> impl Future<Item = (Self, Option<(Vec<u8>, Stat)>), Error = failure::Error> {
         self.get_data_w(path, Watch::None)
     }
+
+    /// Subscribe to changes in the state of the connection to the ensemble.
+    ///
+    /// Unlike the watcher stream returned by [`connect`](#method.connect),
+    /// the returned [`Subscription`] is not tied to any particular node --
+    /// it reports every transition the connection makes (for example, into
+    /// and out of [`KeeperState::Disconnected`] while reconnecting). A new
+    /// subscription only sees states entered after it was created.
+    pub fn subscribe(&self) -> Subscription {
+        Subscription(self.connection.subscribe_state())
+    }
+}
+
+/// A live subscription to a [`ZooKeeper`] connection's state changes,
+/// obtained from [`ZooKeeper::subscribe`](struct.ZooKeeper.html#method.subscribe).
+pub struct Subscription(mpsc::UnboundedReceiver<KeeperState>);
+
+impl Stream for Subscription {
+    type Item = KeeperState;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<KeeperState>, ()> {
+        self.0.poll()
+    }
 }
 
 pub struct WatchGlobally(ZooKeeper);
@@ -281,6 +468,139 @@ impl WithWatcher {
     }
 }
 
+/// A single successfully-applied operation within a committed [`Multi`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MultiResult {
+    /// The result of a queued `create`: the path of the node that was
+    /// created.
+    Create(String),
+    /// The result of a queued `delete`.
+    Delete,
+    /// The result of a queued `set_data`: the node's new `Stat`.
+    SetData(Stat),
+    /// The result of a queued `check`.
+    Check,
+}
+
+/// A builder for a multi-op transaction, constructed with
+/// [`ZooKeeper::multi`](struct.ZooKeeper.html#method.multi).
+///
+/// Queue up any number of operations with [`create`](#method.create),
+/// [`delete`](#method.delete), [`set_data`](#method.set_data), and
+/// [`check`](#method.check), then send them all to the server at once with
+/// [`run`](#method.run). The server applies every queued operation
+/// atomically: if any of them would fail, none of them take effect.
+pub struct Multi {
+    zk: ZooKeeper,
+    ops: Vec<proto::MultiRequest>,
+}
+
+impl Multi {
+    /// Queue the creation of a node at `path` with the given `data` and `acl`.
+    pub fn create<D, A>(mut self, path: &str, data: D, acl: A, mode: CreateMode) -> Self
+    where
+        D: Into<Cow<'static, [u8]>>,
+        A: Into<Cow<'static, [Acl]>>,
+    {
+        self.ops.push(proto::MultiRequest::Create {
+            path: path.to_string(),
+            data: data.into(),
+            acl: acl.into(),
+            mode,
+        });
+        self
+    }
+
+    /// Queue the deletion of the node at `path`, failing if its version does
+    /// not match `version`.
+    pub fn delete(mut self, path: &str, version: Option<i32>) -> Self {
+        self.ops.push(proto::MultiRequest::Delete {
+            path: path.to_string(),
+            version: version.unwrap_or(-1),
+        });
+        self
+    }
+
+    /// Queue setting the data of the node at `path`, failing if its version
+    /// does not match `version`.
+    pub fn set_data<D>(mut self, path: &str, version: Option<i32>, data: D) -> Self
+    where
+        D: Into<Cow<'static, [u8]>>,
+    {
+        self.ops.push(proto::MultiRequest::SetData {
+            path: path.to_string(),
+            data: data.into(),
+            version: version.unwrap_or(-1),
+        });
+        self
+    }
+
+    /// Queue a check that the node at `path` has the given `version`,
+    /// without reading or writing any of its state. Useful to make another
+    /// queued operation conditional on this one.
+    pub fn check(mut self, path: &str, version: i32) -> Self {
+        self.ops.push(proto::MultiRequest::Check {
+            path: path.to_string(),
+            version,
+        });
+        self
+    }
+
+    /// Send the queued operations to the server as a single atomic
+    /// transaction.
+    ///
+    /// The result has one entry per queued operation, in order. If the
+    /// transaction failed, every entry is `Err`: the one operation that
+    /// actually caused the failure carries the reason, while the rest
+    /// carry the fact that they were rolled back alongside it.
+    pub fn run(
+        self,
+    ) -> impl Future<Item = (ZooKeeper, Vec<Result<MultiResult, error::Multi>>), Error = failure::Error>
+    {
+        let Multi { zk, ops } = self;
+        let is_check: Vec<bool> = ops
+            .iter()
+            .map(|op| match *op {
+                proto::MultiRequest::Check { .. } => true,
+                _ => false,
+            })
+            .collect();
+        zk.connection
+            .enqueue(proto::Request::Multi(ops))
+            .and_then(move |r| match r {
+                Ok(proto::Response::Multi(results)) => {
+                    // The op that actually failed carries its real error;
+                    // every other op is rolled back and reported with a
+                    // placeholder `RuntimeInconsistency`. Point every
+                    // reported failure at the former, not whichever op
+                    // happens to come first.
+                    let failed_index = results.iter().position(|r| match *r {
+                        Err(e) => e != ZkError::RuntimeInconsistency,
+                        Ok(_) => false,
+                    });
+                    let mut out = Vec::with_capacity(results.len());
+                    for (index, result) in results.into_iter().enumerate() {
+                        out.push(match result {
+                            Ok(proto::Response::String(s)) => Ok(MultiResult::Create(s)),
+                            Ok(proto::Response::SetData { stat }) => Ok(MultiResult::SetData(stat)),
+                            Ok(proto::Response::Empty) if is_check[index] => Ok(MultiResult::Check),
+                            Ok(proto::Response::Empty) => Ok(MultiResult::Delete),
+                            Ok(r) => bail!("got unexpected response to multi-op: {:?}", r),
+                            Err(e) => Err(error::Multi {
+                                index: failed_index.unwrap_or(index),
+                                source: format!("{:?}", e),
+                            }),
+                        });
+                    }
+                    Ok(out)
+                }
+                Ok(r) => bail!("got non-multi response to multi: {:?}", r),
+                Err(e) => Err(format_err!("multi call failed: {:?}", e)),
+            })
+            .map(move |r| (zk, r))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -290,7 +610,7 @@ mod tests {
         let mut rt = tokio::runtime::Runtime::new().unwrap();
         let (zk, w): (ZooKeeper, _) =
             rt.block_on(
-                ZooKeeper::connect(&"127.0.0.1:2181".parse().unwrap()).and_then(|(zk, w)| {
+                ZooKeeper::connect("127.0.0.1:2181").and_then(|(zk, w)| {
                     zk.with_watcher()
                         .exists("/foo")
                         .inspect(|(_, _, stat)| assert_eq!(stat, &None))
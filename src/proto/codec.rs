@@ -0,0 +1,86 @@
+//! Framing for requests and replies, layered on top of
+//! `tokio_codec`'s `LengthDelimitedCodec`.
+//!
+//! Every ZooKeeper frame on the wire is a 4-byte big-endian length prefix
+//! followed by that many bytes of payload -- exactly what
+//! `LengthDelimitedCodec` already implements. `PacketCodec` only has to
+//! worry about what's inside that payload: an already-serialized request
+//! body going out, and (with one exception) a reply header (xid, zxid, err)
+//! followed by an opcode-specific body coming in.
+//!
+//! The exception is the very first reply on a freshly established
+//! connection -- the bare `ConnectResponse`, both on the initial connection
+//! and on every reconnect -- which carries no header at all. `PacketCodec`
+//! tracks that per-connection and hands it back as [`Frame::Connect`]
+//! instead of forcing a header split that isn't there.
+
+use byteorder::{BigEndian, ReadBytesExt};
+use bytes::BytesMut;
+use std::io;
+use tokio_codec::{Decoder, Encoder, LengthDelimitedCodec};
+
+/// The header every reply frame but the first is tagged with, ahead of its
+/// opcode-specific body.
+pub(crate) struct ReplyHeader {
+    pub(crate) xid: i32,
+    pub(crate) zxid: i64,
+    pub(crate) err: i32,
+}
+
+/// A decoded reply frame.
+pub(crate) enum Frame {
+    /// The bare `ConnectResponse` body -- the one reply that is not preceded
+    /// by a [`ReplyHeader`].
+    Connect(BytesMut),
+    /// Every other reply, tagged with its header.
+    Reply(ReplyHeader, BytesMut),
+}
+
+/// Encodes pre-serialized request frame bodies (built by
+/// [`super::frame_body`] or [`super::frame_connect_body`]) and decodes reply
+/// frames, delegating the length-prefix framing itself to
+/// `LengthDelimitedCodec`.
+pub(crate) struct PacketCodec {
+    inner: LengthDelimitedCodec,
+    /// Whether the next frame decoded off the wire is this connection's
+    /// headerless `ConnectResponse`.
+    before_connect: bool,
+}
+
+impl PacketCodec {
+    pub(crate) fn new() -> Self {
+        PacketCodec {
+            inner: LengthDelimitedCodec::new(),
+            before_connect: true,
+        }
+    }
+}
+
+impl Encoder for PacketCodec {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn encode(&mut self, body: Vec<u8>, dst: &mut BytesMut) -> io::Result<()> {
+        self.inner.encode(body.into(), dst)
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Frame;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Self::Item>> {
+        let mut frame = match self.inner.decode(src)? {
+            Some(frame) => frame,
+            None => return Ok(None),
+        };
+        if self.before_connect {
+            self.before_connect = false;
+            return Ok(Some(Frame::Connect(frame)));
+        }
+        let xid = frame.split_to(4).as_ref().read_i32::<BigEndian>()?;
+        let zxid = frame.split_to(8).as_ref().read_i64::<BigEndian>()?;
+        let err = frame.split_to(4).as_ref().read_i32::<BigEndian>()?;
+        Ok(Some(Frame::Reply(ReplyHeader { xid, zxid, err }, frame)))
+    }
+}
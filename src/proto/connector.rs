@@ -0,0 +1,63 @@
+//! Strategies for (re-)establishing the transport stream a [`super::Packetizer`]
+//! frames its traffic over.
+//!
+//! A `Packetizer<S>` never dials a server itself: the very first connection
+//! is made through a [`Connector`] before the `Packetizer` is built, and the
+//! `Packetizer` asks that same `Connector` every time the connection needs
+//! to be re-established. This is what lets the
+//! same framing and dispatch logic run over either a plain `TcpStream` or a
+//! TLS-wrapped one.
+
+use futures::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+
+/// Something that can (re-)establish a connection of type `S` to a given
+/// address.
+pub(crate) trait Connector<S>: Send {
+    fn connect(&self, addr: SocketAddr) -> Box<Future<Item = S, Error = io::Error> + Send>;
+}
+
+/// Connects over plain, unencrypted TCP.
+pub(crate) struct PlainConnector;
+
+impl Connector<TcpStream> for PlainConnector {
+    fn connect(&self, addr: SocketAddr) -> Box<Future<Item = TcpStream, Error = io::Error> + Send> {
+        Box::new(TcpStream::connect(&addr))
+    }
+}
+
+/// Connects over TCP, then negotiates TLS on top of it via `tokio-rustls`,
+/// authenticating the server against `server_name`.
+pub(crate) struct TlsStreamConnector {
+    pub(crate) connector: tokio_rustls::TlsConnector,
+    pub(crate) server_name: webpki::DNSName,
+}
+
+impl Connector<tokio_rustls::TlsStream<TcpStream, rustls::ClientSession>> for TlsStreamConnector {
+    fn connect(
+        &self,
+        addr: SocketAddr,
+    ) -> Box<
+        Future<Item = tokio_rustls::TlsStream<TcpStream, rustls::ClientSession>, Error = io::Error>
+            + Send,
+    > {
+        let connector = self.connector.clone();
+        let server_name = self.server_name.clone();
+        Box::new(
+            TcpStream::connect(&addr)
+                .and_then(move |stream| connector.connect(server_name.as_ref(), stream)),
+        )
+    }
+}
+
+impl TlsStreamConnector {
+    pub(crate) fn new(config: Arc<rustls::ClientConfig>, server_name: webpki::DNSName) -> Self {
+        TlsStreamConnector {
+            connector: tokio_rustls::TlsConnector::from(config),
+            server_name,
+        }
+    }
+}
@@ -0,0 +1,871 @@
+//! The wire protocol and the connection that speaks it.
+//!
+//! Everything in this module is private to the crate: callers only ever see
+//! the public API in `lib.rs`, which is built on top of the
+//! [`Enqueuer`]/[`Packetizer`] pair defined here.
+
+mod codec;
+mod connector;
+mod request;
+mod response;
+
+use self::codec::{Frame, PacketCodec, ReplyHeader};
+pub(crate) use self::connector::{Connector, PlainConnector, TlsStreamConnector};
+pub(crate) use self::request::{MultiRequest, OpCode, Request};
+pub(crate) use self::response::{Response, ZkError};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::BytesMut;
+use futures::sync::{mpsc, oneshot};
+use futures::AsyncSink;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io::{self, Cursor, Read};
+use std::mem;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::prelude::*;
+use tokio_codec::{Decoder, Framed};
+use types::{KeeperState, WatchedEvent};
+
+/// The xid the server uses on replies to watch notifications, which aren't
+/// replies to any request we sent.
+const NOTIFICATION_XID: i32 = -1;
+/// The xid we use for heartbeat pings, matching the reference client.
+const PING_XID: i32 = -2;
+/// The xid reserved for `Auth` packets, matching the reference client. Like
+/// pings, these don't get a slot in `outstanding` -- callers waiting on a
+/// reply are tracked separately, in `pending_auths`.
+const AUTH_XID: i32 = -4;
+
+/// The kind of watch that can be registered against a node.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub(crate) enum WatchType {
+    Exist,
+    Child,
+    Data,
+}
+
+/// What, if anything, should happen when the node being operated on changes.
+pub(crate) enum Watch {
+    /// Do not watch this node.
+    None,
+    /// Route a watch event for this node to the connection's default
+    /// watcher stream.
+    Global,
+    /// Route a watch event for this node to this one-shot receiver.
+    Custom(oneshot::Sender<WatchedEvent>),
+}
+
+impl Watch {
+    pub(crate) fn is_set(&self) -> bool {
+        match *self {
+            Watch::None => false,
+            Watch::Global | Watch::Custom(_) => true,
+        }
+    }
+}
+
+impl ::std::fmt::Debug for Watch {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Watch::None => write!(f, "Watch::None"),
+            Watch::Global => write!(f, "Watch::Global"),
+            Watch::Custom(_) => write!(f, "Watch::Custom(..)"),
+        }
+    }
+}
+
+/// A request queued up to be sent to the server, along with the channel its
+/// response (or failure) should be delivered on.
+struct Enqueued {
+    request: Request,
+    reply: oneshot::Sender<Result<Response, ZkError>>,
+}
+
+/// Something sent to a `Packetizer` over its inbox: either a request to be
+/// sent to the server, or a request to subscribe to connection-state
+/// changes.
+enum Message {
+    Request(Enqueued),
+    Subscribe(mpsc::UnboundedSender<KeeperState>),
+}
+
+/// A cheaply-cloneable handle to a live connection's [`Packetizer`].
+///
+/// Dropping every `Enqueuer` for a connection causes its `Packetizer` to shut
+/// the connection down once all in-flight requests have been answered.
+#[derive(Clone)]
+pub(crate) struct Enqueuer(mpsc::UnboundedSender<Message>);
+
+impl Enqueuer {
+    pub(crate) fn enqueue(
+        &self,
+        request: Request,
+    ) -> impl Future<Item = Result<Response, ZkError>, Error = failure::Error> {
+        let (tx, rx) = oneshot::channel();
+        let r = self.0.unbounded_send(Message::Request(Enqueued {
+            request,
+            reply: tx,
+        }));
+        future::result(r.map_err(|e| format_err!("packetizer is gone: {:?}", e)))
+            .and_then(|_| rx.map_err(|e| format_err!("connection to zookeeper was lost: {:?}", e)))
+    }
+
+    /// Subscribe to changes in the state of the connection to the ensemble.
+    ///
+    /// The subscription does not get an initial event for the connection's
+    /// current state -- only for states entered after the subscription is
+    /// created.
+    pub(crate) fn subscribe_state(&self) -> mpsc::UnboundedReceiver<KeeperState> {
+        let (tx, rx) = mpsc::unbounded();
+        let _ = self.0.unbounded_send(Message::Subscribe(tx));
+        rx
+    }
+}
+
+enum WatchSender {
+    Global,
+    Custom(oneshot::Sender<WatchedEvent>),
+}
+
+impl WatchSender {
+    fn send(self, default_watcher: &mpsc::UnboundedSender<WatchedEvent>, event: WatchedEvent) {
+        match self {
+            WatchSender::Global => {
+                let _ = default_watcher.unbounded_send(event);
+            }
+            WatchSender::Custom(tx) => {
+                let _ = tx.send(event);
+            }
+        }
+    }
+}
+
+/// A request that has been sent to the server (or is about to be, if we are
+/// currently reconnecting) and is awaiting a reply.
+struct Outstanding {
+    opcode: OpCode,
+    /// The serialized frame body (xid, opcode, and request body, but not the
+    /// length prefix `PacketCodec` adds), kept around so it can be resent
+    /// verbatim if the connection drops before a reply arrives.
+    frame: Vec<u8>,
+    reply: oneshot::Sender<Result<Response, ZkError>>,
+}
+
+/// The negotiated state of a session with the ensemble, as established by
+/// the initial `Connect` reply. Kept around so that a dropped TCP connection
+/// can be transparently re-established against the same session.
+#[derive(Default)]
+struct Session {
+    session_id: i64,
+    passwd: Vec<u8>,
+    timeout: Duration,
+    last_zxid_seen: i64,
+}
+
+impl Session {
+    /// The negotiated session timeout in milliseconds, as the wire format
+    /// expects it on a reconnect's `Connect` request. `Duration::as_secs`
+    /// alone would truncate sub-second precision.
+    fn timeout_millis(&self) -> i32 {
+        (self.timeout.as_secs() * 1000 + u64::from(self.timeout.subsec_millis())) as i32
+    }
+}
+
+/// Either a live, framed connection, or a connection attempt that is still
+/// in flight (because the previous one failed).
+enum Conn<S> {
+    Connected(Framed<S, PacketCodec>),
+    Connecting(Box<Future<Item = S, Error = io::Error> + Send>),
+}
+
+/// The connection to a ZooKeeper ensemble, talking to one of its servers at
+/// a time.
+///
+/// This is a `Future` that is spawned onto the `tokio` runtime by
+/// `Packetizer::new`; it drives the connection for as long as it is alive,
+/// transparently reconnecting and replaying any unacknowledged requests if
+/// the underlying connection is lost, and stops once there are no more
+/// `Enqueuer`s and no outstanding requests.
+///
+/// `S` is the transport the connection is framed over -- a plain
+/// `tokio::net::TcpStream` or a TLS stream wrapping one -- and `connector`
+/// is what knows how to (re-)establish one of those.
+pub(crate) struct Packetizer<S> {
+    /// Every server in the ensemble, as resolved from the connect string.
+    addrs: Vec<SocketAddr>,
+    /// The index into `addrs` that `conn` is (or is about to be) connected
+    /// to.
+    addr_idx: usize,
+    /// The root every path is implicitly relative to, if a chroot was given
+    /// in the connect string.
+    chroot: Option<String>,
+    conn: Conn<S>,
+    connector: Box<Connector<S>>,
+
+    inbox: mpsc::UnboundedReceiver<Message>,
+    default_watcher: mpsc::UnboundedSender<WatchedEvent>,
+    state_subscribers: Vec<mpsc::UnboundedSender<KeeperState>>,
+
+    xid: i32,
+    /// Frame bodies waiting to be handed to the `Sink` half of `conn`.
+    write_queue: VecDeque<Vec<u8>>,
+    outstanding: BTreeMap<i32, Outstanding>,
+    watches: HashMap<(WatchType, String), Vec<WatchSender>>,
+
+    /// The reply channel for a caller-initiated `Connect`, i.e. the very
+    /// first connection a `Packetizer` makes. `None` once that reply has
+    /// arrived, and for every subsequent reconnect, which replays `Connect`
+    /// on its own behalf with no caller waiting on it.
+    connect_reply: Option<oneshot::Sender<Result<Response, ZkError>>>,
+
+    /// Every `(scheme, auth)` pair ever passed to `add_auth`, so they can be
+    /// resent after a reconnect -- the server does not remember them across
+    /// a new TCP connection, even for the same session.
+    auth_packets: Vec<(String, Vec<u8>)>,
+    /// Reply channels for `Auth` packets that are still awaiting a
+    /// response, in the order their requests were sent.
+    pending_auths: VecDeque<oneshot::Sender<Result<Response, ZkError>>>,
+
+    session: Session,
+    connected: bool,
+    ping: Option<tokio::timer::Interval>,
+}
+
+impl<S> Packetizer<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Construct a new `Packetizer` for an ensemble whose servers are given
+    /// by `addrs`, given an already-established `stream` to `addrs[0]` and
+    /// the `connector` that can re-establish a stream of the same kind on
+    /// reconnect.
+    pub(crate) fn new(
+        addrs: Vec<SocketAddr>,
+        chroot: Option<String>,
+        stream: S,
+        connector: Box<Connector<S>>,
+        default_watcher: mpsc::UnboundedSender<WatchedEvent>,
+    ) -> Enqueuer {
+        let (tx, rx) = mpsc::unbounded();
+        let packetizer = Packetizer {
+            addrs,
+            addr_idx: 0,
+            chroot,
+            conn: Conn::Connected(PacketCodec::new().framed(stream)),
+            connector,
+            inbox: rx,
+            default_watcher,
+            state_subscribers: Vec::new(),
+            xid: 0,
+            write_queue: VecDeque::new(),
+            outstanding: BTreeMap::new(),
+            watches: HashMap::new(),
+            connect_reply: None,
+            auth_packets: Vec::new(),
+            pending_auths: VecDeque::new(),
+            session: Session::default(),
+            connected: false,
+            ping: None,
+        };
+        tokio::spawn(packetizer.map_err(|_| ()));
+        Enqueuer(tx)
+    }
+
+    /// Notify every live subscriber that the connection entered `state`,
+    /// dropping any subscriber whose receiver has gone away.
+    fn notify_state(&mut self, state: KeeperState) {
+        self.state_subscribers
+            .retain(|tx| tx.unbounded_send(state).is_ok());
+    }
+
+    /// Prepend `self.chroot`, if any, to every path in `request`.
+    fn apply_chroot(&self, request: &mut Request) {
+        apply_chroot(&self.chroot, request);
+    }
+
+    /// Strip `self.chroot`, if any, from a path the server handed back to us
+    /// (e.g. the path returned from a sequential `create`, or the path named
+    /// in a watch notification).
+    fn strip_chroot(&self, path: String) -> String {
+        strip_chroot(&self.chroot, path)
+    }
+
+    fn queue(&mut self, mut item: Enqueued) {
+        if let Request::Connect { .. } = item.request {
+            // Unlike every other request, `Connect` carries no xid/opcode
+            // header on the wire -- see `frame_connect_body`.
+            self.write_queue.push_back(frame_connect_body(&item.request));
+            self.connect_reply = Some(item.reply);
+            return;
+        }
+
+        if let Request::Auth { ref scheme, ref auth } = item.request {
+            self.auth_packets.push((scheme.clone(), auth.clone()));
+            self.write_queue.push_back(frame_body(AUTH_XID, &item.request));
+            self.pending_auths.push_back(item.reply);
+            return;
+        }
+
+        let xid = self.xid;
+        self.xid += 1;
+
+        let watch_target = watch_info(&item.request).map(|(path, wtype)| (path.to_string(), wtype));
+        self.apply_chroot(&mut item.request);
+        let watch = take_watch(&mut item.request);
+        match (watch_target, watch) {
+            (Some((path, wtype)), Watch::Global) => {
+                self.watches
+                    .entry((wtype, path))
+                    .or_insert_with(Vec::new)
+                    .push(WatchSender::Global);
+            }
+            (Some((path, wtype)), Watch::Custom(tx)) => {
+                self.watches
+                    .entry((wtype, path))
+                    .or_insert_with(Vec::new)
+                    .push(WatchSender::Custom(tx));
+            }
+            _ => {}
+        }
+
+        let frame = frame_body(xid, &item.request);
+        self.write_queue.push_back(frame.clone());
+        self.outstanding.insert(
+            xid,
+            Outstanding {
+                opcode: item.request.opcode(),
+                frame,
+                reply: item.reply,
+            },
+        );
+    }
+
+    /// Send a heartbeat ping if enough time has passed since the session was
+    /// established, so that the ensemble does not time out our session
+    /// while we are otherwise idle.
+    fn maintain_session(&mut self) -> io::Result<()> {
+        if !self.connected {
+            return Ok(());
+        }
+        if self.ping.is_none() {
+            let period = self.session.timeout / 3;
+            self.ping = Some(tokio::timer::Interval::new(Instant::now() + period, period));
+        }
+        loop {
+            match self.ping.as_mut().unwrap().poll() {
+                Ok(Async::Ready(Some(_))) => {
+                    self.write_queue.push_back(frame_body(PING_XID, &Request::Ping));
+                }
+                Ok(Async::Ready(None)) | Ok(Async::NotReady) => break,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::Other, e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Advance to the next server in the ensemble, wrapping back to the
+    /// first once the last is reached, and return its address.
+    fn next_addr(&mut self) -> SocketAddr {
+        self.addr_idx = (self.addr_idx + 1) % self.addrs.len();
+        self.addrs[self.addr_idx]
+    }
+
+    /// Begin (re)connecting to the next server in the ensemble. Once the
+    /// connection succeeds, the session is re-established and every
+    /// outstanding request is replayed on the new connection.
+    fn begin_reconnect(&mut self) {
+        self.connected = false;
+        self.ping = None;
+        self.write_queue.clear();
+        let addr = self.next_addr();
+        self.conn = Conn::Connecting(self.connector.connect(addr));
+        self.notify_state(KeeperState::Disconnected);
+    }
+
+    fn on_reconnected(&mut self) {
+        let reconnect = Request::Connect {
+            protocol_version: 0,
+            last_zxid_seen: self.session.last_zxid_seen,
+            timeout: self.session.timeout_millis(),
+            session_id: self.session.session_id,
+            passwd: self.session.passwd.clone(),
+            read_only: false,
+        };
+        self.write_queue.push_back(frame_connect_body(&reconnect));
+        for (scheme, auth) in &self.auth_packets {
+            let request = Request::Auth {
+                scheme: scheme.clone(),
+                auth: auth.clone(),
+            };
+            self.write_queue.push_back(frame_body(AUTH_XID, &request));
+        }
+        for outstanding in self.outstanding.values() {
+            self.write_queue.push_back(outstanding.frame.clone());
+        }
+    }
+
+    fn try_write(&mut self) -> io::Result<()> {
+        let framed = match self.conn {
+            Conn::Connected(ref mut framed) => framed,
+            Conn::Connecting(_) => return Ok(()),
+        };
+        while let Some(frame) = self.write_queue.pop_front() {
+            match framed.start_send(frame)? {
+                AsyncSink::Ready => {}
+                AsyncSink::NotReady(frame) => {
+                    self.write_queue.push_front(frame);
+                    break;
+                }
+            }
+        }
+        framed.poll_complete()?;
+        Ok(())
+    }
+
+    fn try_read(&mut self) -> io::Result<()> {
+        loop {
+            let framed = match self.conn {
+                Conn::Connected(ref mut framed) => framed,
+                Conn::Connecting(_) => return Ok(()),
+            };
+            match framed.poll()? {
+                Async::Ready(Some(Frame::Connect(body))) => self.process_connect_reply(body)?,
+                Async::Ready(Some(Frame::Reply(header, body))) => self.process_packet(header, body)?,
+                Async::Ready(None) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "zookeeper server closed the connection",
+                    ));
+                }
+                Async::NotReady => return Ok(()),
+            }
+        }
+    }
+
+    /// Handle the one reply that carries no `ReplyHeader`: the
+    /// `ConnectResponse` that is always the first thing read off a freshly
+    /// established connection, whether this is the very first connection or
+    /// a reconnect.
+    fn process_connect_reply(&mut self, body: BytesMut) -> io::Result<()> {
+        let response = Response::parse(OpCode::Connect, &body)?;
+        self.remember_session(response.clone());
+        if let Some(reply) = self.connect_reply.take() {
+            let _ = reply.send(Ok(response));
+        }
+        Ok(())
+    }
+
+    fn process_packet(&mut self, header: ReplyHeader, body: BytesMut) -> io::Result<()> {
+        let ReplyHeader { xid, zxid, err } = header;
+        if zxid > 0 {
+            self.session.last_zxid_seen = zxid;
+        }
+
+        if xid == NOTIFICATION_XID {
+            return self.handle_notification(&mut Cursor::new(&body[..]));
+        }
+        if xid == PING_XID {
+            return Ok(());
+        }
+
+        if xid == AUTH_XID {
+            let result = match ZkError::from_wire(err) {
+                Some(e) => {
+                    self.notify_state(KeeperState::AuthFailed);
+                    Err(e)
+                }
+                None => Ok(Response::Empty),
+            };
+            if let Some(reply) = self.pending_auths.pop_front() {
+                let _ = reply.send(result);
+            }
+            return Ok(());
+        }
+
+        let outstanding = self.outstanding.remove(&xid);
+        let opcode = outstanding
+            .as_ref()
+            .map(|o| o.opcode)
+            .unwrap_or(OpCode::Connect);
+        let result = match ZkError::from_wire(err) {
+            Some(e) => Err(e),
+            None => {
+                let response = Response::parse(opcode, &body)?;
+                if let Response::Connect { .. } = response {
+                    self.remember_session(response.clone());
+                }
+                Ok(self.strip_chroot_from_response(response))
+            }
+        };
+        if let Some(outstanding) = outstanding {
+            let _ = outstanding.reply.send(result);
+        }
+        Ok(())
+    }
+
+    /// Strip `self.chroot` from any path embedded in `response`, so that
+    /// callers never see the chroot we transparently prepended on the way
+    /// out.
+    fn strip_chroot_from_response(&self, response: Response) -> Response {
+        if self.chroot.is_none() {
+            return response;
+        }
+        match response {
+            Response::String(path) => Response::String(self.strip_chroot(path)),
+            Response::Multi(results) => Response::Multi(
+                results
+                    .into_iter()
+                    .map(|r| r.map(|r| self.strip_chroot_from_response(r)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    fn remember_session(&mut self, response: Response) {
+        if let Response::Connect {
+            timeout,
+            session_id,
+            passwd,
+            ..
+        } = response
+        {
+            // A `Connect` reply with a zero session id means the session we
+            // asked the server to resume is gone -- most commonly because it
+            // sat disconnected past its negotiated timeout. There is no
+            // session left to reconnect to, so report the expiry instead of
+            // quietly treating it as a fresh, healthy connection.
+            if session_id == 0 {
+                self.connected = false;
+                self.notify_state(KeeperState::Expired);
+                return;
+            }
+            self.session.session_id = session_id;
+            self.session.passwd = passwd;
+            self.session.timeout = Duration::from_millis(timeout.max(0) as u64);
+            self.connected = true;
+            self.ping = None;
+            self.notify_state(KeeperState::SyncConnected);
+        }
+    }
+
+    fn handle_notification(&mut self, cursor: &mut Cursor<&[u8]>) -> io::Result<()> {
+        let event_type = cursor.read_i32::<BigEndian>()?;
+        let keeper_state = cursor.read_i32::<BigEndian>()?;
+        let mut rest = Vec::new();
+        cursor.read_to_end(&mut rest)?;
+        let mut rest_cursor = Cursor::new(&rest[..]);
+        let path_len = rest_cursor.read_i32::<BigEndian>()? as usize;
+        let mut path_buf = vec![0; path_len];
+        rest_cursor.read_exact(&mut path_buf)?;
+        let path = String::from_utf8_lossy(&path_buf).into_owned();
+        let path = self.strip_chroot(path);
+
+        let keeper_state = wire_to_keeper_state(keeper_state);
+        self.notify_state(keeper_state);
+
+        let event = WatchedEvent {
+            event_type: wire_to_event_type(event_type),
+            keeper_state,
+            path: path.clone(),
+        };
+        for wtype in &[WatchType::Exist, WatchType::Data, WatchType::Child] {
+            if let Some(senders) = self.watches.remove(&(*wtype, path.clone())) {
+                for sender in senders {
+                    sender.send(&self.default_watcher, event.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S> Future for Packetizer<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    type Item = ();
+    type Error = failure::Error;
+
+    fn poll(&mut self) -> Poll<(), failure::Error> {
+        loop {
+            match self.inbox.poll() {
+                Ok(Async::Ready(Some(Message::Request(item)))) => self.queue(item),
+                Ok(Async::Ready(Some(Message::Subscribe(tx)))) => {
+                    self.state_subscribers.push(tx);
+                }
+                Ok(Async::Ready(None)) => {
+                    if self.outstanding.is_empty() && self.write_queue.is_empty() {
+                        return Ok(Async::Ready(()));
+                    }
+                    break;
+                }
+                Ok(Async::NotReady) => break,
+                Err(_) => break,
+            }
+        }
+
+        loop {
+            let poll_result = match self.conn {
+                Conn::Connecting(ref mut fut) => Some(fut.poll()),
+                Conn::Connected(_) => None,
+            };
+            match poll_result {
+                None => break,
+                Some(Ok(Async::Ready(stream))) => {
+                    self.conn = Conn::Connected(PacketCodec::new().framed(stream));
+                    self.on_reconnected();
+                    break;
+                }
+                Some(Ok(Async::NotReady)) => return Ok(Async::NotReady),
+                Some(Err(_)) => {
+                    // Try the next server in the ensemble, polling it
+                    // immediately rather than returning with a future that
+                    // has never been polled (and so could park forever).
+                    let addr = self.next_addr();
+                    self.conn = Conn::Connecting(self.connector.connect(addr));
+                }
+            }
+        }
+
+        let io_result = self
+            .try_write()
+            .and_then(|_| self.try_read())
+            .and_then(|_| self.maintain_session());
+        if let Err(e) = io_result {
+            self.begin_reconnect();
+            return self.poll();
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+/// Serialize `request`'s xid, opcode, and body into a frame body -- that is,
+/// everything `PacketCodec` will wrap in a length prefix before it goes out
+/// on the wire.
+fn frame_body(xid: i32, request: &Request) -> Vec<u8> {
+    let mut frame = Vec::new();
+    frame.write_i32::<BigEndian>(xid).unwrap();
+    frame.write_i32::<BigEndian>(request.opcode() as i32).unwrap();
+    request.serialize_into(&mut frame);
+    frame
+}
+
+/// Serialize `request`'s body alone, with no xid/opcode prefix. Unlike every
+/// other request, a `Connect` request -- on both the initial connection and
+/// every reconnect -- is just the bare `ConnectRequest`, matching the
+/// headerless `ConnectResponse` `PacketCodec` expects back
+/// (see [`codec::Frame`]).
+fn frame_connect_body(request: &Request) -> Vec<u8> {
+    let mut frame = Vec::new();
+    request.serialize_into(&mut frame);
+    frame
+}
+
+/// Prepend `chroot`, if any, to every path in `request`.
+fn apply_chroot(chroot: &Option<String>, request: &mut Request) {
+    let chroot = match *chroot {
+        Some(ref chroot) => chroot,
+        None => return,
+    };
+    // The root of the client's view is the chroot itself, not chroot + "/".
+    let prefix = |path: &mut String| {
+        *path = if *path == "/" {
+            chroot.clone()
+        } else {
+            format!("{}{}", chroot, path)
+        };
+    };
+    match *request {
+        Request::Create { ref mut path, .. }
+        | Request::Delete { ref mut path, .. }
+        | Request::Exists { ref mut path, .. }
+        | Request::GetData { ref mut path, .. }
+        | Request::SetData { ref mut path, .. }
+        | Request::GetAcl { ref mut path, .. }
+        | Request::SetAcl { ref mut path, .. }
+        | Request::GetChildren { ref mut path, .. } => {
+            prefix(path);
+        }
+        Request::Multi(ref mut ops) => {
+            for op in ops {
+                let path = match *op {
+                    MultiRequest::Create { ref mut path, .. }
+                    | MultiRequest::Delete { ref mut path, .. }
+                    | MultiRequest::SetData { ref mut path, .. }
+                    | MultiRequest::Check { ref mut path, .. } => path,
+                };
+                prefix(path);
+            }
+        }
+        Request::Connect { .. } | Request::Auth { .. } | Request::Ping => {}
+    }
+}
+
+/// Strip `chroot`, if any, from a path the server handed back to us (e.g.
+/// the path returned from a sequential `create`, or the path named in a
+/// watch notification).
+fn strip_chroot(chroot: &Option<String>, path: String) -> String {
+    match *chroot {
+        Some(ref chroot) if path.starts_with(chroot.as_str()) => {
+            match path.as_bytes().get(chroot.len()) {
+                // A sibling path that merely happens to start with the same
+                // bytes as `chroot` (e.g. "/abc" against a chroot of "/a")
+                // is not actually under it -- leave it alone.
+                Some(b) if *b != b'/' => path,
+                None => "/".to_string(),
+                _ => path[chroot.len()..].to_string(),
+            }
+        }
+        _ => path,
+    }
+}
+
+fn watch_info(request: &Request) -> Option<(&str, WatchType)> {
+    match *request {
+        Request::Exists { ref path, .. } => Some((path, WatchType::Exist)),
+        Request::GetData { ref path, .. } => Some((path, WatchType::Data)),
+        Request::GetChildren { ref path, .. } => Some((path, WatchType::Child)),
+        _ => None,
+    }
+}
+
+fn take_watch(request: &mut Request) -> Watch {
+    match *request {
+        Request::Exists { ref mut watch, .. }
+        | Request::GetData { ref mut watch, .. }
+        | Request::GetChildren { ref mut watch, .. } => mem::replace(watch, Watch::None),
+        _ => Watch::None,
+    }
+}
+
+fn wire_to_event_type(code: i32) -> ::types::WatchedEventType {
+    use types::WatchedEventType::*;
+    match code {
+        1 => NodeCreated,
+        2 => NodeDeleted,
+        3 => NodeDataChanged,
+        4 => NodeChildrenChanged,
+        _ => NodeDataChanged,
+    }
+}
+
+fn wire_to_keeper_state(code: i32) -> ::types::KeeperState {
+    use types::KeeperState::*;
+    match code {
+        0 => Disconnected,
+        3 => SyncConnected,
+        4 => AuthFailed,
+        5 => ConnectedReadOnly,
+        6 => SaslAuthenticated,
+        -112 => Expired,
+        _ => SyncConnected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::CreateMode;
+
+    #[test]
+    fn apply_chroot_without_chroot_is_a_no_op() {
+        let mut request = Request::GetData {
+            path: "/foo".to_string(),
+            watch: Watch::None,
+        };
+        apply_chroot(&None, &mut request);
+        match request {
+            Request::GetData { ref path, .. } => assert_eq!(path, "/foo"),
+            _ => panic!("unexpected request"),
+        }
+    }
+
+    #[test]
+    fn apply_chroot_prepends_to_every_op_path() {
+        let chroot = Some("/a/b".to_string());
+        let mut request = Request::Multi(vec![
+            MultiRequest::Create {
+                path: "/foo".to_string(),
+                data: Vec::new().into(),
+                acl: Vec::new().into(),
+                mode: CreateMode::Persistent,
+            },
+            MultiRequest::Check {
+                path: "/bar".to_string(),
+                version: 0,
+            },
+        ]);
+        apply_chroot(&chroot, &mut request);
+        match request {
+            Request::Multi(ops) => {
+                match ops[0] {
+                    MultiRequest::Create { ref path, .. } => assert_eq!(path, "/a/b/foo"),
+                    _ => panic!("unexpected op"),
+                }
+                match ops[1] {
+                    MultiRequest::Check { ref path, .. } => assert_eq!(path, "/a/b/bar"),
+                    _ => panic!("unexpected op"),
+                }
+            }
+            _ => panic!("unexpected request"),
+        }
+    }
+
+    #[test]
+    fn apply_chroot_leaves_connect_and_auth_and_ping_untouched() {
+        for mut request in vec![
+            Request::Connect {
+                protocol_version: 0,
+                last_zxid_seen: 0,
+                timeout: 0,
+                session_id: 0,
+                passwd: Vec::new(),
+                read_only: false,
+            },
+            Request::Auth {
+                scheme: "digest".to_string(),
+                auth: Vec::new(),
+            },
+            Request::Ping,
+        ] {
+            // Should not panic, and should leave the request's shape alone.
+            let before = format!("{:?}", request);
+            apply_chroot(&Some("/a".to_string()), &mut request);
+            assert_eq!(format!("{:?}", request), before);
+        }
+    }
+
+    #[test]
+    fn strip_chroot_without_chroot_is_a_no_op() {
+        assert_eq!(strip_chroot(&None, "/foo/bar".to_string()), "/foo/bar");
+    }
+
+    #[test]
+    fn strip_chroot_removes_the_prefix() {
+        let chroot = Some("/a/b".to_string());
+        assert_eq!(strip_chroot(&chroot, "/a/b/foo".to_string()), "/foo");
+    }
+
+    #[test]
+    fn strip_chroot_of_the_chroot_itself_is_root() {
+        let chroot = Some("/a/b".to_string());
+        assert_eq!(strip_chroot(&chroot, "/a/b".to_string()), "/");
+    }
+
+    #[test]
+    fn strip_chroot_leaves_unrelated_paths_alone() {
+        let chroot = Some("/a/b".to_string());
+        assert_eq!(strip_chroot(&chroot, "/c/d".to_string()), "/c/d");
+    }
+
+    #[test]
+    fn strip_chroot_leaves_a_sibling_with_a_shared_prefix_alone() {
+        let chroot = Some("/a".to_string());
+        assert_eq!(strip_chroot(&chroot, "/abc".to_string()), "/abc");
+    }
+}
@@ -0,0 +1,352 @@
+use super::Watch;
+use byteorder::{BigEndian, WriteBytesExt};
+use std::borrow::Cow;
+use types::{Acl, CreateMode};
+
+/// The opcodes used to tag requests on the wire.
+///
+/// These mirror the `ZooDefs.OpCode` constants from the reference ZooKeeper
+/// client.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum OpCode {
+    Create = 1,
+    Delete = 2,
+    Exists = 3,
+    GetData = 4,
+    SetData = 5,
+    GetAcl = 6,
+    SetAcl = 7,
+    GetChildren = 8,
+    Multi = 14,
+    Auth = 100,
+    Connect = 0,
+    Ping = 11,
+}
+
+/// A single top-level request, sent to the server as its own packet. The
+/// operations batched into a [`Request::Multi`] are [`MultiRequest`]s.
+#[derive(Debug)]
+pub(crate) enum Request {
+    Connect {
+        protocol_version: i32,
+        last_zxid_seen: i64,
+        timeout: i32,
+        session_id: i64,
+        passwd: Vec<u8>,
+        read_only: bool,
+    },
+    Create {
+        path: String,
+        data: Cow<'static, [u8]>,
+        acl: Cow<'static, [Acl]>,
+        mode: CreateMode,
+    },
+    Delete {
+        path: String,
+        version: i32,
+    },
+    Exists {
+        path: String,
+        watch: Watch,
+    },
+    GetData {
+        path: String,
+        watch: Watch,
+    },
+    SetData {
+        path: String,
+        data: Cow<'static, [u8]>,
+        version: i32,
+    },
+    GetAcl {
+        path: String,
+    },
+    SetAcl {
+        path: String,
+        acl: Cow<'static, [Acl]>,
+        version: i32,
+    },
+    GetChildren {
+        path: String,
+        watch: Watch,
+    },
+    Multi(Vec<MultiRequest>),
+    Auth {
+        scheme: String,
+        auth: Vec<u8>,
+    },
+    Ping,
+}
+
+/// A single operation that can be batched into a
+/// [`Request::Multi`] transaction.
+#[derive(Debug)]
+pub(crate) enum MultiRequest {
+    Create {
+        path: String,
+        data: Cow<'static, [u8]>,
+        acl: Cow<'static, [Acl]>,
+        mode: CreateMode,
+    },
+    Delete {
+        path: String,
+        version: i32,
+    },
+    SetData {
+        path: String,
+        data: Cow<'static, [u8]>,
+        version: i32,
+    },
+    Check {
+        path: String,
+        version: i32,
+    },
+}
+
+impl OpCode {
+    pub(crate) fn from_wire(code: i32) -> Self {
+        match code {
+            1 => OpCode::Create,
+            2 => OpCode::Delete,
+            3 => OpCode::Exists,
+            4 => OpCode::GetData,
+            5 => OpCode::SetData,
+            6 => OpCode::GetAcl,
+            7 => OpCode::SetAcl,
+            8 => OpCode::GetChildren,
+            14 => OpCode::Multi,
+            100 => OpCode::Auth,
+            11 => OpCode::Ping,
+            _ => OpCode::Connect,
+        }
+    }
+}
+
+impl Request {
+    pub(crate) fn opcode(&self) -> OpCode {
+        match *self {
+            Request::Connect { .. } => OpCode::Connect,
+            Request::Create { .. } => OpCode::Create,
+            Request::Delete { .. } => OpCode::Delete,
+            Request::Exists { .. } => OpCode::Exists,
+            Request::GetData { .. } => OpCode::GetData,
+            Request::SetData { .. } => OpCode::SetData,
+            Request::GetAcl { .. } => OpCode::GetAcl,
+            Request::SetAcl { .. } => OpCode::SetAcl,
+            Request::GetChildren { .. } => OpCode::GetChildren,
+            Request::Multi(..) => OpCode::Multi,
+            Request::Auth { .. } => OpCode::Auth,
+            Request::Ping => OpCode::Ping,
+        }
+    }
+
+    /// Serialize this request's body (not including the connect/request
+    /// header, which `Packetizer` is responsible for) into `buf`.
+    pub(crate) fn serialize_into(&self, buf: &mut Vec<u8>) {
+        match *self {
+            Request::Connect {
+                protocol_version,
+                last_zxid_seen,
+                timeout,
+                session_id,
+                ref passwd,
+                read_only,
+            } => {
+                buf.write_i32::<BigEndian>(protocol_version).unwrap();
+                buf.write_i64::<BigEndian>(last_zxid_seen).unwrap();
+                buf.write_i32::<BigEndian>(timeout).unwrap();
+                buf.write_i64::<BigEndian>(session_id).unwrap();
+                write_buffer(buf, passwd);
+                buf.push(read_only as u8);
+            }
+            Request::Create {
+                ref path,
+                ref data,
+                ref acl,
+                mode,
+            } => {
+                write_string(buf, path);
+                write_buffer(buf, data);
+                write_acl(buf, acl);
+                buf.write_i32::<BigEndian>(mode.to_wire_flag()).unwrap();
+            }
+            Request::Delete { ref path, version } => {
+                write_string(buf, path);
+                buf.write_i32::<BigEndian>(version).unwrap();
+            }
+            Request::Exists { ref path, ref watch } | Request::GetData { ref path, ref watch } => {
+                write_string(buf, path);
+                buf.push(watch.is_set() as u8);
+            }
+            Request::SetData {
+                ref path,
+                ref data,
+                version,
+            } => {
+                write_string(buf, path);
+                write_buffer(buf, data);
+                buf.write_i32::<BigEndian>(version).unwrap();
+            }
+            Request::GetAcl { ref path } => {
+                write_string(buf, path);
+            }
+            Request::SetAcl {
+                ref path,
+                ref acl,
+                version,
+            } => {
+                write_string(buf, path);
+                write_acl(buf, acl);
+                buf.write_i32::<BigEndian>(version).unwrap();
+            }
+            Request::GetChildren { ref path, ref watch } => {
+                write_string(buf, path);
+                buf.push(watch.is_set() as u8);
+            }
+            Request::Multi(ref ops) => {
+                for op in ops {
+                    buf.write_i32::<BigEndian>(op.header_opcode()).unwrap();
+                    buf.push(0); // done
+                    buf.write_i32::<BigEndian>(-1).unwrap(); // err, unused on the way in
+                    op.serialize_into(buf);
+                }
+                // The terminating `MultiHeader` that tells the server there
+                // are no more ops to come.
+                buf.write_i32::<BigEndian>(-1).unwrap();
+                buf.push(1); // done
+                buf.write_i32::<BigEndian>(-1).unwrap();
+            }
+            Request::Auth {
+                ref scheme,
+                ref auth,
+            } => {
+                buf.write_i32::<BigEndian>(0).unwrap(); // auth type, always 0
+                write_string(buf, scheme);
+                write_buffer(buf, auth);
+            }
+            Request::Ping => {}
+        }
+    }
+}
+
+impl MultiRequest {
+    /// The opcode a `MultiHeader` tags this op with, mirroring the
+    /// `ZooDefs.OpCode` constants from the reference client. `check` has no
+    /// top-level `OpCode` of its own, since it is only ever valid inside a
+    /// `Multi`.
+    fn header_opcode(&self) -> i32 {
+        match *self {
+            MultiRequest::Create { .. } => OpCode::Create as i32,
+            MultiRequest::Delete { .. } => OpCode::Delete as i32,
+            MultiRequest::SetData { .. } => OpCode::SetData as i32,
+            MultiRequest::Check { .. } => 13,
+        }
+    }
+
+    fn serialize_into(&self, buf: &mut Vec<u8>) {
+        match *self {
+            MultiRequest::Create {
+                ref path,
+                ref data,
+                ref acl,
+                mode,
+            } => {
+                write_string(buf, path);
+                write_buffer(buf, data);
+                write_acl(buf, acl);
+                buf.write_i32::<BigEndian>(mode.to_wire_flag()).unwrap();
+            }
+            MultiRequest::Delete { ref path, version } => {
+                write_string(buf, path);
+                buf.write_i32::<BigEndian>(version).unwrap();
+            }
+            MultiRequest::SetData {
+                ref path,
+                ref data,
+                version,
+            } => {
+                write_string(buf, path);
+                write_buffer(buf, data);
+                buf.write_i32::<BigEndian>(version).unwrap();
+            }
+            MultiRequest::Check { ref path, version } => {
+                write_string(buf, path);
+                buf.write_i32::<BigEndian>(version).unwrap();
+            }
+        }
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    write_buffer(buf, s.as_bytes());
+}
+
+fn write_buffer(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.write_i32::<BigEndian>(bytes.len() as i32).unwrap();
+    buf.extend_from_slice(bytes);
+}
+
+fn write_acl(buf: &mut Vec<u8>, acl: &[Acl]) {
+    buf.write_i32::<BigEndian>(acl.len() as i32).unwrap();
+    for entry in acl {
+        buf.write_i32::<BigEndian>(entry.perms.to_wire()).unwrap();
+        write_string(buf, &entry.scheme);
+        write_string(buf, &entry.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::ReadBytesExt;
+    use std::io::{self, Read};
+
+    #[test]
+    fn multi_request_is_framed_with_a_header_per_op_and_a_terminator() {
+        let request = Request::Multi(vec![
+            MultiRequest::Delete {
+                path: "/foo".to_string(),
+                version: -1,
+            },
+            MultiRequest::Check {
+                path: "/bar".to_string(),
+                version: 3,
+            },
+        ]);
+        let mut buf = Vec::new();
+        request.serialize_into(&mut buf);
+        let mut cursor = io::Cursor::new(&buf[..]);
+
+        // Delete op: header, then path and version.
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), OpCode::Delete as i32);
+        assert_eq!(cursor.read_u8().unwrap(), 0);
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), -1);
+        assert_eq!(read_string(&mut cursor), "/foo");
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), -1);
+
+        // Check op: header (opcode 13, the reference client's `OpCode.check`,
+        // which has no top-level `OpCode` variant of its own), then path and
+        // version.
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), 13);
+        assert_eq!(cursor.read_u8().unwrap(), 0);
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), -1);
+        assert_eq!(read_string(&mut cursor), "/bar");
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), 3);
+
+        // The terminating header that tells the server there are no more ops.
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), -1);
+        assert_eq!(cursor.read_u8().unwrap(), 1);
+        assert_eq!(cursor.read_i32::<BigEndian>().unwrap(), -1);
+
+        let mut rest = Vec::new();
+        cursor.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    }
+
+    fn read_string(cursor: &mut io::Cursor<&[u8]>) -> String {
+        let len = cursor.read_i32::<BigEndian>().unwrap() as usize;
+        let mut buf = vec![0; len];
+        cursor.read_exact(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
@@ -0,0 +1,264 @@
+use byteorder::{BigEndian, ReadBytesExt};
+use std::io::{self, Read};
+use types::{Acl, Permission, Stat};
+
+/// The error codes the ZooKeeper ensemble can attach to a response, as
+/// mirrored from the reference client's `KeeperException.Code`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum ZkError {
+    SystemError,
+    RuntimeInconsistency,
+    DataInconsistency,
+    ConnectionLoss,
+    MarshallingError,
+    Unimplemented,
+    OperationTimeout,
+    BadArguments,
+    ApiError,
+    NoNode,
+    NoAuth,
+    BadVersion,
+    NoChildrenForEphemerals,
+    NodeExists,
+    NotEmpty,
+    SessionExpired,
+    InvalidCallback,
+    InvalidAcl,
+    AuthFailed,
+    SessionMoved,
+    NotReadOnly,
+    Unknown(i32),
+}
+
+impl ZkError {
+    pub(crate) fn from_wire(code: i32) -> Option<Self> {
+        if code == 0 {
+            return None;
+        }
+        Some(match code {
+            -1 => ZkError::SystemError,
+            -2 => ZkError::RuntimeInconsistency,
+            -3 => ZkError::DataInconsistency,
+            -4 => ZkError::ConnectionLoss,
+            -5 => ZkError::MarshallingError,
+            -6 => ZkError::Unimplemented,
+            -7 => ZkError::OperationTimeout,
+            -8 => ZkError::BadArguments,
+            -100 => ZkError::ApiError,
+            -101 => ZkError::NoNode,
+            -102 => ZkError::NoAuth,
+            -103 => ZkError::BadVersion,
+            -108 => ZkError::NoChildrenForEphemerals,
+            -110 => ZkError::NodeExists,
+            -111 => ZkError::NotEmpty,
+            -112 => ZkError::SessionExpired,
+            -113 => ZkError::InvalidCallback,
+            -114 => ZkError::InvalidAcl,
+            -115 => ZkError::AuthFailed,
+            -118 => ZkError::SessionMoved,
+            -119 => ZkError::NotReadOnly,
+            other => ZkError::Unknown(other),
+        })
+    }
+}
+
+/// The body of a successful response, tagged by the request that produced it.
+#[derive(Debug, Clone)]
+pub(crate) enum Response {
+    Empty,
+    String(String),
+    Strings(Vec<String>),
+    Exists { stat: Stat },
+    GetData { bytes: Vec<u8>, stat: Stat },
+    SetData { stat: Stat },
+    GetAcl { acl: Vec<Acl>, stat: Stat },
+    SetAcl { stat: Stat },
+    Multi(Vec<Result<Response, ZkError>>),
+    Connect {
+        protocol_version: i32,
+        timeout: i32,
+        session_id: i64,
+        passwd: Vec<u8>,
+    },
+}
+
+impl Response {
+    pub(crate) fn parse(opcode: super::OpCode, buf: &[u8]) -> io::Result<Self> {
+        let mut cursor = io::Cursor::new(buf);
+        match opcode {
+            super::OpCode::Create => Ok(Response::String(read_string(&mut cursor)?)),
+            super::OpCode::Delete => Ok(Response::Empty),
+            super::OpCode::Exists => Ok(Response::Exists {
+                stat: read_stat(&mut cursor)?,
+            }),
+            super::OpCode::GetData => {
+                let bytes = read_buffer(&mut cursor)?;
+                let stat = read_stat(&mut cursor)?;
+                Ok(Response::GetData { bytes, stat })
+            }
+            super::OpCode::SetData => Ok(Response::SetData {
+                stat: read_stat(&mut cursor)?,
+            }),
+            super::OpCode::GetAcl => {
+                let acl = read_acl(&mut cursor)?;
+                let stat = read_stat(&mut cursor)?;
+                Ok(Response::GetAcl { acl, stat })
+            }
+            super::OpCode::SetAcl => Ok(Response::SetAcl {
+                stat: read_stat(&mut cursor)?,
+            }),
+            super::OpCode::GetChildren => {
+                let n = cursor.read_i32::<BigEndian>()?;
+                let mut children = Vec::with_capacity(n as usize);
+                for _ in 0..n {
+                    children.push(read_string(&mut cursor)?);
+                }
+                Ok(Response::Strings(children))
+            }
+            super::OpCode::Multi => {
+                let mut results = Vec::new();
+                loop {
+                    let op_opcode = cursor.read_i32::<BigEndian>()?;
+                    let done = cursor.read_u8()? != 0;
+                    let err = cursor.read_i32::<BigEndian>()?;
+                    if done {
+                        break;
+                    }
+                    if let Some(e) = ZkError::from_wire(err) {
+                        results.push(Err(e));
+                        continue;
+                    }
+                    let opcode = super::OpCode::from_wire(op_opcode);
+                    results.push(Ok(Response::parse_one(opcode, &mut cursor)?));
+                }
+                Ok(Response::Multi(results))
+            }
+            super::OpCode::Connect => {
+                let protocol_version = cursor.read_i32::<BigEndian>()?;
+                let timeout = cursor.read_i32::<BigEndian>()?;
+                let session_id = cursor.read_i64::<BigEndian>()?;
+                let passwd = read_buffer(&mut cursor)?;
+                Ok(Response::Connect {
+                    protocol_version,
+                    timeout,
+                    session_id,
+                    passwd,
+                })
+            }
+            super::OpCode::Auth => Ok(Response::Empty),
+            super::OpCode::Ping => Ok(Response::Empty),
+        }
+    }
+
+    fn parse_one(opcode: super::OpCode, cursor: &mut io::Cursor<&[u8]>) -> io::Result<Response> {
+        match opcode {
+            super::OpCode::Create => Ok(Response::String(read_string(cursor)?)),
+            super::OpCode::Delete => Ok(Response::Empty),
+            super::OpCode::SetData => Ok(Response::SetData {
+                stat: read_stat(cursor)?,
+            }),
+            _ => Ok(Response::Empty),
+        }
+    }
+}
+
+fn read_buffer<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let len = r.read_i32::<BigEndian>()?;
+    if len < 0 {
+        return Ok(Vec::new());
+    }
+    let mut buf = vec![0; len as usize];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    let bytes = read_buffer(r)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_acl<R: Read>(r: &mut R) -> io::Result<Vec<Acl>> {
+    let n = r.read_i32::<BigEndian>()?;
+    let mut acl = Vec::with_capacity(n as usize);
+    for _ in 0..n {
+        let perms = Permission::from_wire(r.read_i32::<BigEndian>()?);
+        let scheme = read_string(r)?;
+        let id = read_string(r)?;
+        acl.push(Acl {
+            perms,
+            scheme: scheme.into(),
+            id: id.into(),
+        });
+    }
+    Ok(acl)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    fn write_header(buf: &mut Vec<u8>, opcode: i32, done: bool, err: i32) {
+        buf.write_i32::<BigEndian>(opcode).unwrap();
+        buf.push(done as u8);
+        buf.write_i32::<BigEndian>(err).unwrap();
+    }
+
+    #[test]
+    fn parses_a_successful_multi_reply() {
+        let mut buf = Vec::new();
+        write_header(&mut buf, super::super::OpCode::Delete as i32, false, 0);
+        write_header(&mut buf, 13, false, 0); // OpCode.check, no body on success
+        write_header(&mut buf, -1, true, -1); // terminator
+
+        let results = match Response::parse(super::super::OpCode::Multi, &buf).unwrap() {
+            Response::Multi(results) => results,
+            other => panic!("expected a multi response, got {:?}", other),
+        };
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            match result {
+                Ok(Response::Empty) => {}
+                other => panic!("expected an empty response, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn parses_a_failed_multi_reply_with_the_culprit_and_its_rollbacks() {
+        let mut buf = Vec::new();
+        write_header(&mut buf, super::super::OpCode::Create as i32, false, -101); // NoNode
+        write_header(&mut buf, super::super::OpCode::Delete as i32, false, -2); // rolled back
+        write_header(&mut buf, -1, true, -1); // terminator
+
+        let results = match Response::parse(super::super::OpCode::Multi, &buf).unwrap() {
+            Response::Multi(results) => results,
+            other => panic!("expected a multi response, got {:?}", other),
+        };
+        assert_eq!(results.len(), 2);
+        match results[0] {
+            Err(ZkError::NoNode) => {}
+            ref other => panic!("expected NoNode, got {:?}", other),
+        }
+        match results[1] {
+            Err(ZkError::RuntimeInconsistency) => {}
+            ref other => panic!("expected RuntimeInconsistency, got {:?}", other),
+        }
+    }
+}
+
+fn read_stat<R: Read>(r: &mut R) -> io::Result<Stat> {
+    Ok(Stat {
+        czxid: r.read_i64::<BigEndian>()?,
+        mzxid: r.read_i64::<BigEndian>()?,
+        ctime: r.read_i64::<BigEndian>()?,
+        mtime: r.read_i64::<BigEndian>()?,
+        version: r.read_i32::<BigEndian>()?,
+        cversion: r.read_i32::<BigEndian>()?,
+        aversion: r.read_i32::<BigEndian>()?,
+        ephemeral_owner: r.read_i64::<BigEndian>()?,
+        data_length: r.read_i32::<BigEndian>()?,
+        num_children: r.read_i32::<BigEndian>()?,
+        pzxid: r.read_i64::<BigEndian>()?,
+    })
+}
@@ -0,0 +1,165 @@
+//! Types shared between the public API and the wire protocol.
+
+use std::borrow::Cow;
+
+/// The mode a node is created in, which controls its lifetime and naming.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CreateMode {
+    /// The znode will not be automatically deleted.
+    Persistent,
+    /// The znode will not be automatically deleted, and its name will have a
+    /// monotonically increasing sequence number appended to it.
+    PersistentSequential,
+    /// The znode will be deleted automatically when the creating session ends.
+    Ephemeral,
+    /// Like `Ephemeral`, but with a monotonically increasing sequence number
+    /// appended to the given name.
+    EphemeralSequential,
+}
+
+impl CreateMode {
+    pub(crate) fn to_wire_flag(&self) -> i32 {
+        match *self {
+            CreateMode::Persistent => 0,
+            CreateMode::Ephemeral => 1,
+            CreateMode::PersistentSequential => 2,
+            CreateMode::EphemeralSequential => 3,
+        }
+    }
+}
+
+/// The permissions associated with an [`Acl`](struct.Acl.html), as a bitmask.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Permission(i32);
+
+impl Permission {
+    /// You can read a node's value, and list its children.
+    pub const READ: Permission = Permission(1 << 0);
+    /// You can set a node's value.
+    pub const WRITE: Permission = Permission(1 << 1);
+    /// You can create children.
+    pub const CREATE: Permission = Permission(1 << 2);
+    /// You can delete children.
+    pub const DELETE: Permission = Permission(1 << 3);
+    /// You can alter permissions.
+    pub const ADMIN: Permission = Permission(1 << 4);
+    /// All of the above.
+    pub const ALL: Permission =
+        Permission(Self::READ.0 | Self::WRITE.0 | Self::CREATE.0 | Self::DELETE.0 | Self::ADMIN.0);
+
+    pub(crate) fn to_wire(&self) -> i32 {
+        self.0
+    }
+
+    pub(crate) fn from_wire(bits: i32) -> Self {
+        Permission(bits)
+    }
+}
+
+impl ::std::ops::BitOr for Permission {
+    type Output = Permission;
+    fn bitor(self, rhs: Permission) -> Permission {
+        Permission(self.0 | rhs.0)
+    }
+}
+
+/// An access control entry, binding a set of [`Permission`]s to an identity
+/// under some authentication scheme.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Acl {
+    /// The permissions associated with this ACL entry.
+    pub perms: Permission,
+    /// The authentication scheme this ACL entry is good for (e.g. `"world"`,
+    /// `"auth"`, `"digest"`, `"ip"`).
+    pub scheme: Cow<'static, str>,
+    /// The identity under the given `scheme` that is granted `perms`.
+    pub id: Cow<'static, str>,
+}
+
+impl Acl {
+    /// An ACL that grants all permissions to anyone.
+    pub fn open_unsafe() -> Cow<'static, [Self]> {
+        Cow::Borrowed(&[Acl {
+            perms: Permission::ALL,
+            scheme: Cow::Borrowed("world"),
+            id: Cow::Borrowed("anyone"),
+        }])
+    }
+
+    /// An ACL that grants read-only access to anyone.
+    pub fn read_unsafe() -> Cow<'static, [Self]> {
+        Cow::Borrowed(&[Acl {
+            perms: Permission::READ,
+            scheme: Cow::Borrowed("world"),
+            id: Cow::Borrowed("anyone"),
+        }])
+    }
+
+    /// An ACL that grants all permissions to the creator of the znode only.
+    pub fn creator_all() -> Cow<'static, [Self]> {
+        Cow::Borrowed(&[Acl {
+            perms: Permission::ALL,
+            scheme: Cow::Borrowed("auth"),
+            id: Cow::Borrowed(""),
+        }])
+    }
+}
+
+/// Metadata about a znode, as maintained by the ZooKeeper ensemble.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Stat {
+    /// The zxid of the change that caused this znode to be created.
+    pub czxid: i64,
+    /// The zxid of the change that last modified this znode.
+    pub mzxid: i64,
+    /// The time in milliseconds from epoch when this znode was created.
+    pub ctime: i64,
+    /// The time in milliseconds from epoch when this znode was last modified.
+    pub mtime: i64,
+    /// The number of changes to the data of this znode.
+    pub version: i32,
+    /// The number of changes to the children of this znode.
+    pub cversion: i32,
+    /// The number of changes to the ACL of this znode.
+    pub aversion: i32,
+    /// The session id of the owner of this znode, if it is ephemeral, or 0.
+    pub ephemeral_owner: i64,
+    /// The length of the data field of this znode.
+    pub data_length: i32,
+    /// The number of children of this znode.
+    pub num_children: i32,
+    /// The zxid of the change that last modified the children of this znode.
+    pub pzxid: i64,
+}
+
+/// The kind of event a [`WatchedEvent`](struct.WatchedEvent.html) reports.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WatchedEventType {
+    NodeCreated,
+    NodeDeleted,
+    NodeDataChanged,
+    NodeChildrenChanged,
+}
+
+/// The state of the connection to the ZooKeeper ensemble at the time a
+/// [`WatchedEvent`](struct.WatchedEvent.html) was raised.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KeeperState {
+    Disconnected,
+    SyncConnected,
+    AuthFailed,
+    ConnectedReadOnly,
+    SaslAuthenticated,
+    Expired,
+}
+
+/// An event pertaining to a watch previously registered with the ensemble.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WatchedEvent {
+    /// What happened.
+    pub event_type: WatchedEventType,
+    /// What the state of the connection was when it happened.
+    pub keeper_state: KeeperState,
+    /// The path of the znode the watch was registered on.
+    pub path: String,
+}